@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Helpers for binding IP-based sockets to a local endpoint, shared by the
+//! stream and datagram socket types.
+
+use aster_bigtcp::{
+    iface::BindPortConfig,
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
+};
+
+use crate::{
+    net::iface::{get_iface, Iface},
+    prelude::*,
+};
+
+/// Returns an ephemeral local endpoint suitable for reaching `remote_endpoint`.
+///
+/// The address is left unspecified (`INADDR_ANY`/`in6addr_any`), so
+/// [`bind_socket`] binds on the default interface; the interface is not
+/// chosen by a route to the peer. The port is left as `0` so the iface
+/// allocates the first free ephemeral port.
+pub fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> IpEndpoint {
+    let unspecified_addr = match remote_endpoint.addr {
+        IpAddress::Ipv4(_) => IpAddress::Ipv4(Ipv4Address::UNSPECIFIED),
+        IpAddress::Ipv6(_) => IpAddress::Ipv6(Ipv6Address::UNSPECIFIED),
+    };
+    IpEndpoint::new(unspecified_addr, 0)
+}
+
+/// Binds `socket` to `endpoint`, calling `bind` once the target interface is
+/// known to perform the iface-specific bind operation.
+///
+/// If `endpoint`'s address is unspecified (i.e., `INADDR_ANY`/`in6addr_any`),
+/// the default interface is picked, but the unspecified address is passed
+/// through to `bind` unchanged rather than being replaced with that
+/// interface's concrete address. This keeps the bound socket's local
+/// endpoint itself unspecified, so it accepts traffic addressed to any of
+/// the interface's local addresses, which is what listening sockets need
+/// for `listen()` without a prior `bind()` to behave like `INADDR_ANY`.
+pub fn bind_socket<T, R>(
+    socket: T,
+    endpoint: &IpEndpoint,
+    can_reuse: bool,
+    bind: impl FnOnce(&Iface, T, BindPortConfig) -> core::result::Result<R, (Error, T)>,
+) -> core::result::Result<R, (Error, T)> {
+    let iface = match get_iface_to_bind(&endpoint.addr) {
+        Some(iface) => iface,
+        None => {
+            return Err((
+                Error::with_message(
+                    Errno::EADDRNOTAVAIL,
+                    "no interface matches the requested address",
+                ),
+                socket,
+            ));
+        }
+    };
+
+    let bind_port_config = BindPortConfig::new(endpoint.port, can_reuse);
+    bind(&iface, socket, bind_port_config)
+}
+
+/// Returns the interface that a socket binding to `addr` should use.
+///
+/// An unspecified `addr` (`INADDR_ANY`/`in6addr_any`) resolves to the
+/// system's default interface; the wildcard semantics (accepting traffic
+/// addressed to any of that interface's local addresses) are handled by the
+/// caller, which binds the port on that interface without restricting it to
+/// a single local address. Other interfaces are not bound.
+fn get_iface_to_bind(addr: &IpAddress) -> Option<Arc<Iface>> {
+    if is_unspecified(addr) {
+        get_iface(None)
+    } else {
+        get_iface(Some(addr))
+    }
+}
+
+fn is_unspecified(addr: &IpAddress) -> bool {
+    match addr {
+        IpAddress::Ipv4(addr) => *addr == Ipv4Address::UNSPECIFIED,
+        IpAddress::Ipv6(addr) => *addr == Ipv6Address::UNSPECIFIED,
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn ephemeral_endpoint_keeps_family_and_clears_port() {
+        let remote_v4 = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::new(10, 0, 0, 1)), 80);
+        let local_v4 = get_ephemeral_endpoint(&remote_v4);
+        assert_eq!(local_v4.addr, IpAddress::Ipv4(Ipv4Address::UNSPECIFIED));
+        assert_eq!(local_v4.port, 0);
+
+        let remote_v6 = IpEndpoint::new(
+            IpAddress::Ipv6(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            443,
+        );
+        let local_v6 = get_ephemeral_endpoint(&remote_v6);
+        assert_eq!(local_v6.addr, IpAddress::Ipv6(Ipv6Address::UNSPECIFIED));
+        assert_eq!(local_v6.port, 0);
+    }
+
+    #[ktest]
+    fn is_unspecified_distinguishes_wildcard_from_concrete() {
+        assert!(is_unspecified(&IpAddress::Ipv4(Ipv4Address::UNSPECIFIED)));
+        assert!(is_unspecified(&IpAddress::Ipv6(Ipv6Address::UNSPECIFIED)));
+
+        assert!(!is_unspecified(&IpAddress::Ipv4(Ipv4Address::new(
+            127, 0, 0, 1
+        ))));
+        assert!(!is_unspecified(&IpAddress::Ipv6(Ipv6Address::new(
+            0, 0, 0, 0, 0, 0, 0, 1
+        ))));
+    }
+}
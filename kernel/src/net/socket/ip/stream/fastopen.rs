@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! TCP Fast Open (TFO) key used to verify cookies on the listening path.
+//!
+//! A listener that opts in via `setsockopt(TCP_FASTOPEN, qlen)` sets this key on each backlog
+//! socket via `UnboundTcpSocket::set_fastopen_key`; `aster_bigtcp` itself generates the cookie
+//! handed out to connecting peers and verifies it against the key when a later SYN presents one,
+//! so a valid cookie lets that SYN's payload be queued right away instead of waiting for the
+//! handshake to complete before any data can be accepted.
+
+use ostd::{random::random, sync::Once};
+
+static FASTOPEN_KEY: Once<u64> = Once::new();
+
+/// Returns the key used to verify Fast Open cookies for the lifetime of this boot.
+///
+/// The key only needs to authenticate cookies handed out during this boot, so deriving it once,
+/// lazily, is enough; there is no need to persist it across reboots. It must come from a real
+/// entropy source, though: this is what makes a Fast Open cookie unforgeable by an off-path
+/// attacker, so a value derived from predictable inputs (uptime, a fixed virtual address) would
+/// defeat the whole point of requiring one.
+pub fn key() -> u64 {
+    *FASTOPEN_KEY.call_once(random)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn key_is_stable_across_calls() {
+        assert_eq!(key(), key());
+    }
+}
@@ -2,7 +2,7 @@
 
 use aster_bigtcp::{
     socket::{RawTcpSetOption, UnboundTcpSocket},
-    wire::IpEndpoint,
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
 };
 
 use super::{connecting::ConnectingStream, listen::ListenStream, StreamObserver};
@@ -16,18 +16,88 @@ use crate::{
     process::signal::Pollee,
 };
 
-pub enum InitStream {
+pub struct InitStream {
+    state: State,
+    /// Whether this socket is restricted to IPv6-only traffic (the `IPV6_V6ONLY` option).
+    ///
+    /// This only has an effect once the socket is bound to the IPv6 wildcard address; see
+    /// [`ListenStream`] for how it is used.
+    v6only: bool,
+    /// Whether this socket was created as `AF_INET6` rather than `AF_INET`.
+    ///
+    /// A `State::Bound` socket's local address (even a wildcard one) already fixes its family, so
+    /// this is only consulted by [`Self::listen`]'s auto-bind path: an `AF_INET6` socket that
+    /// calls `listen()` without a prior `bind()` must be bound to the IPv6 wildcard (`::`), not
+    /// `0.0.0.0`.
+    is_ipv6: bool,
+    /// The TCP Fast Open queue length set via `setsockopt(TCP_FASTOPEN, qlen)` before `listen()`.
+    ///
+    /// Linux allows `TCP_FASTOPEN` to be set either before or after `listen()`; this carries a
+    /// pre-`listen()` value forward so that the backlog sockets filled as soon as the listener is
+    /// created already have Fast Open enabled, instead of only ones created later by `accept`.
+    fastopen_queue_len: usize,
+}
+
+enum State {
     Unbound(Box<UnboundTcpSocket>),
     Bound(BoundTcpSocket),
 }
 
 impl InitStream {
-    pub fn new() -> Self {
-        InitStream::Unbound(Box::new(UnboundTcpSocket::new()))
+    pub fn new(is_ipv6: bool) -> Self {
+        Self {
+            state: State::Unbound(Box::new(UnboundTcpSocket::new())),
+            v6only: false,
+            is_ipv6,
+            fastopen_queue_len: 0,
+        }
     }
 
     pub fn new_bound(bound_socket: BoundTcpSocket) -> Self {
-        InitStream::Bound(bound_socket)
+        let is_ipv6 = matches!(
+            bound_socket.local_endpoint().unwrap().addr,
+            IpAddress::Ipv6(_)
+        );
+        Self {
+            state: State::Bound(bound_socket),
+            v6only: false,
+            is_ipv6,
+            fastopen_queue_len: 0,
+        }
+    }
+
+    /// Returns whether the `IPV6_V6ONLY` option is set.
+    pub fn v6only(&self) -> bool {
+        self.v6only
+    }
+
+    /// Sets the `IPV6_V6ONLY` option.
+    ///
+    /// Like Linux, this is rejected with `EINVAL` once the socket has been bound, since binding
+    /// (or auto-binding via `listen`) is what decides whether the option has any effect.
+    ///
+    /// This is the socket-layer primitive for `setsockopt(IPPROTO_IPV6, IPV6_V6ONLY, ...)`;
+    /// dispatching that option from the generic `setsockopt` syscall to this method is done by
+    /// the code that implements `setsockopt` for stream sockets.
+    pub fn set_v6only(&mut self, v6only: bool) -> Result<()> {
+        if !matches!(self.state, State::Unbound(_)) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "IPV6_V6ONLY cannot be changed after the socket is bound"
+            );
+        }
+        self.v6only = v6only;
+        Ok(())
+    }
+
+    /// Sets the TCP Fast Open queue length, as requested via `setsockopt(TCP_FASTOPEN, qlen)`
+    /// before the socket starts listening.
+    ///
+    /// This is the socket-layer primitive for that option; dispatching it (and the later,
+    /// already-listening case handled by [`ListenStream::set_fastopen_queue_len`]) from the
+    /// generic `setsockopt` syscall is done by the stream socket's `setsockopt` implementation.
+    pub fn set_fastopen_queue_len(&mut self, qlen: usize) {
+        self.fastopen_queue_len = qlen;
     }
 
     pub fn bind(
@@ -36,12 +106,24 @@ impl InitStream {
         can_reuse: bool,
         observer: StreamObserver,
     ) -> core::result::Result<BoundTcpSocket, (Error, Self)> {
-        let unbound_socket = match self {
-            InitStream::Unbound(unbound_socket) => unbound_socket,
-            InitStream::Bound(bound_socket) => {
+        let Self {
+            state,
+            v6only,
+            is_ipv6,
+            fastopen_queue_len,
+        } = self;
+
+        let unbound_socket = match state {
+            State::Unbound(unbound_socket) => unbound_socket,
+            State::Bound(bound_socket) => {
                 return Err((
                     Error::with_message(Errno::EINVAL, "the socket is already bound to an address"),
-                    InitStream::Bound(bound_socket),
+                    Self {
+                        state: State::Bound(bound_socket),
+                        v6only,
+                        is_ipv6,
+                        fastopen_queue_len,
+                    },
                 ));
             }
         };
@@ -52,7 +134,17 @@ impl InitStream {
             |iface, socket, config| iface.bind_tcp(socket, observer, config),
         ) {
             Ok(bound_socket) => bound_socket,
-            Err((err, unbound_socket)) => return Err((err, InitStream::Unbound(unbound_socket))),
+            Err((err, unbound_socket)) => {
+                return Err((
+                    err,
+                    Self {
+                        state: State::Unbound(unbound_socket),
+                        v6only,
+                        is_ipv6,
+                        fastopen_queue_len,
+                    },
+                ));
+            }
         };
         Ok(bound_socket)
     }
@@ -71,14 +163,47 @@ impl InitStream {
         remote_endpoint: &IpEndpoint,
         pollee: &Pollee,
     ) -> core::result::Result<ConnectingStream, (Error, Self)> {
-        let bound_socket = match self {
-            InitStream::Bound(bound_socket) => bound_socket,
-            InitStream::Unbound(_) => self
+        self.connect_with_fastopen_data(remote_endpoint, &[], pollee)
+    }
+
+    /// Like [`Self::connect`], but additionally carries `data` in the SYN itself.
+    ///
+    /// This is used for TCP Fast Open: `data` comes from the first `sendmsg` call made with
+    /// `MSG_FASTOPEN`, or from data queued ahead of time via `TCP_FASTOPEN_CONNECT`. An empty
+    /// `data` behaves exactly like a normal connect. Wiring those syscall entry points to this
+    /// method is left to the code that implements `sendmsg`/`setsockopt` for stream sockets.
+    pub fn connect_with_fastopen_data(
+        self,
+        remote_endpoint: &IpEndpoint,
+        data: &[u8],
+        pollee: &Pollee,
+    ) -> core::result::Result<ConnectingStream, (Error, Self)> {
+        let v6only = self.v6only;
+        let is_ipv6 = self.is_ipv6;
+        let fastopen_queue_len = self.fastopen_queue_len;
+        let bound_socket = match self.state {
+            State::Bound(bound_socket) => bound_socket,
+            State::Unbound(_) => self
                 .bind_to_ephemeral_endpoint(remote_endpoint, StreamObserver::new(pollee.clone()))?,
         };
 
-        ConnectingStream::new(bound_socket, *remote_endpoint)
-            .map_err(|(err, bound_socket)| (err, InitStream::Bound(bound_socket)))
+        if !data.is_empty() {
+            // Queue the payload before the SYN goes out, so the stack can carry it in the SYN
+            // itself instead of waiting for the handshake to complete before sending anything.
+            bound_socket.enqueue_fastopen_data(data);
+        }
+
+        ConnectingStream::new(bound_socket, *remote_endpoint).map_err(|(err, bound_socket)| {
+            (
+                err,
+                Self {
+                    state: State::Bound(bound_socket),
+                    v6only,
+                    is_ipv6,
+                    fastopen_queue_len,
+                },
+            )
+        })
     }
 
     pub fn listen(
@@ -86,24 +211,50 @@ impl InitStream {
         backlog: usize,
         pollee: &Pollee,
     ) -> core::result::Result<ListenStream, (Error, Self)> {
-        let InitStream::Bound(bound_socket) = self else {
-            // FIXME: The socket should be bound to INADDR_ANY (i.e., 0.0.0.0) with an ephemeral
-            // port. However, INADDR_ANY is not yet supported, so we need to return an error first.
-            debug_assert!(false, "listen() without bind() is not implemented");
-            return Err((
-                Error::with_message(Errno::EINVAL, "listen() without bind() is not implemented"),
-                self,
-            ));
+        let v6only = self.v6only;
+        let is_ipv6 = self.is_ipv6;
+        let fastopen_queue_len = self.fastopen_queue_len;
+        let bound_socket = match self.state {
+            State::Bound(bound_socket) => bound_socket,
+            State::Unbound(unbound_socket) => {
+                // An unbound socket that starts listening is bound to INADDR_ANY (or its IPv6
+                // equivalent, `::`, for an AF_INET6 socket) with an ephemeral port, just as Linux
+                // does.
+                let unspecified_addr = if is_ipv6 {
+                    IpAddress::Ipv6(Ipv6Address::UNSPECIFIED)
+                } else {
+                    IpAddress::Ipv4(Ipv4Address::UNSPECIFIED)
+                };
+                let wildcard_endpoint = IpEndpoint::new(unspecified_addr, 0);
+                Self {
+                    state: State::Unbound(unbound_socket),
+                    v6only,
+                    is_ipv6,
+                    fastopen_queue_len,
+                }
+                .bind(&wildcard_endpoint, false, StreamObserver::new(pollee.clone()))?
+            }
         };
 
-        ListenStream::new(bound_socket, backlog, pollee)
-            .map_err(|(err, bound_socket)| (err, InitStream::Bound(bound_socket)))
+        ListenStream::new(bound_socket, backlog, v6only, fastopen_queue_len, pollee).map_err(
+            |(err, bound_socket)| {
+                (
+                    err,
+                    Self {
+                        state: State::Bound(bound_socket),
+                        v6only,
+                        is_ipv6,
+                        fastopen_queue_len,
+                    },
+                )
+            },
+        )
     }
 
     pub fn local_endpoint(&self) -> Option<IpEndpoint> {
-        match self {
-            InitStream::Unbound(_) => None,
-            InitStream::Bound(bound_socket) => Some(bound_socket.local_endpoint().unwrap()),
+        match &self.state {
+            State::Unbound(_) => None,
+            State::Bound(bound_socket) => Some(bound_socket.local_endpoint().unwrap()),
         }
     }
 
@@ -116,9 +267,46 @@ impl InitStream {
         &mut self,
         set_option: impl Fn(&mut dyn RawTcpSetOption) -> R,
     ) -> R {
-        match self {
-            InitStream::Unbound(unbound_socket) => set_option(unbound_socket.as_mut()),
-            InitStream::Bound(bound_socket) => set_option(bound_socket),
+        match &mut self.state {
+            State::Unbound(unbound_socket) => set_option(unbound_socket.as_mut()),
+            State::Bound(bound_socket) => set_option(bound_socket),
         }
     }
 }
+
+/// Returns whether `endpoint` is the IPv6 wildcard address (`in6addr_any`, i.e., `::`).
+///
+/// A socket bound to this address is the one for which `IPV6_V6ONLY` matters: with the option
+/// off, such a socket also accepts IPv4 peers, exposed as IPv4-mapped IPv6 addresses
+/// (`::ffff:a.b.c.d`). See [`ListenStream`] for where that is implemented.
+pub(super) fn is_ipv6_wildcard(endpoint: &IpEndpoint) -> bool {
+    matches!(endpoint.addr, IpAddress::Ipv6(addr) if addr == Ipv6Address::UNSPECIFIED)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn ipv6_wildcard_is_recognized() {
+        let endpoint = IpEndpoint::new(IpAddress::Ipv6(Ipv6Address::UNSPECIFIED), 80);
+        assert!(is_ipv6_wildcard(&endpoint));
+    }
+
+    #[ktest]
+    fn concrete_ipv6_address_is_not_wildcard() {
+        let endpoint = IpEndpoint::new(
+            IpAddress::Ipv6(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            80,
+        );
+        assert!(!is_ipv6_wildcard(&endpoint));
+    }
+
+    #[ktest]
+    fn ipv4_address_is_not_an_ipv6_wildcard() {
+        let endpoint = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), 80);
+        assert!(!is_ipv6_wildcard(&endpoint));
+    }
+}
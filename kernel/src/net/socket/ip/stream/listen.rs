@@ -1,17 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use aster_bigtcp::{
     errors::tcp::ListenError,
     iface::BindPortConfig,
     socket::{RawTcpSetOption, TcpState, UnboundTcpSocket},
-    wire::IpEndpoint,
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
 };
 use ostd::sync::PreemptDisabled;
 
-use super::{connected::ConnectedStream, StreamObserver};
+use super::{connected::ConnectedStream, fastopen, init::is_ipv6_wildcard, StreamObserver};
 use crate::{
     events::IoEvents,
-    net::iface::{BoundTcpSocket, Iface},
+    net::{
+        iface::{BoundTcpSocket, Iface},
+        socket::ip::common::bind_socket,
+    },
     prelude::*,
     process::signal::Pollee,
 };
@@ -22,21 +27,43 @@ pub struct ListenStream {
     bound_socket: BoundTcpSocket,
     /// Backlog sockets listening at the local endpoint
     backlog_sockets: RwLock<Vec<BacklogSocket>, PreemptDisabled>,
+    /// The TCP Fast Open queue length configured via `setsockopt(TCP_FASTOPEN, qlen)`.
+    ///
+    /// `0` (the default) means Fast Open is off, matching Linux's `fastopenq` being absent until
+    /// the option is set.
+    fastopen_queue_len: AtomicUsize,
+    /// A second, IPv4-only listener bound to the same port, present only for a dual-stack IPv6
+    /// listener (i.e., one bound to `::` with `IPV6_V6ONLY` off). This lets IPv4 peers connect
+    /// to what looks, from outside, like a single IPv6 socket.
+    dual_stack_v4: Option<Box<ListenStream>>,
 }
 
 impl ListenStream {
     pub fn new(
         bound_socket: BoundTcpSocket,
         backlog: usize,
+        v6only: bool,
+        fastopen_queue_len: usize,
         pollee: &Pollee,
     ) -> core::result::Result<Self, (Error, BoundTcpSocket)> {
         const SOMAXCONN: usize = 4096;
         let somaxconn = SOMAXCONN.min(backlog);
 
+        let dual_stack_v4 = if !v6only && is_ipv6_wildcard(&bound_socket.local_endpoint().unwrap())
+        {
+            Self::new_dual_stack_v4(&bound_socket, somaxconn, fastopen_queue_len, pollee)
+                .ok()
+                .map(Box::new)
+        } else {
+            None
+        };
+
         let listen_stream = Self {
             backlog: somaxconn,
             bound_socket,
             backlog_sockets: RwLock::new(Vec::new()),
+            fastopen_queue_len: AtomicUsize::new(fastopen_queue_len),
+            dual_stack_v4,
         };
         if let Err(err) = listen_stream.fill_backlog_sockets(pollee) {
             return Err((err, listen_stream.bound_socket));
@@ -44,6 +71,55 @@ impl ListenStream {
         Ok(listen_stream)
     }
 
+    /// Binds and starts the IPv4-only shadow listener for a dual-stack IPv6 listener bound to
+    /// `::`, listening at the same port on `0.0.0.0`.
+    fn new_dual_stack_v4(
+        ipv6_bound_socket: &BoundTcpSocket,
+        backlog: usize,
+        fastopen_queue_len: usize,
+        pollee: &Pollee,
+    ) -> Result<Self> {
+        let port = ipv6_bound_socket.local_endpoint().unwrap().port;
+        let v4_endpoint = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), port);
+
+        let unbound_socket_v4 = {
+            let mut unbound = UnboundTcpSocket::new();
+            // Otherwise options set on the IPv6 listener (e.g. SO_KEEPALIVE, TCP_NODELAY)
+            // before listen() would never make it onto any connection accepted through this
+            // shadow listener, since set_raw_option only reaches dual_stack_v4 for calls made
+            // after it exists.
+            inherit_raw_options(ipv6_bound_socket, &mut unbound);
+            Box::new(unbound)
+        };
+
+        let observer = StreamObserver::new(pollee.clone());
+        let bound_socket_v4 = bind_socket(
+            unbound_socket_v4,
+            &v4_endpoint,
+            true,
+            |iface, socket, config| iface.bind_tcp(socket, observer, config),
+        )
+        .map_err(|(err, _)| err)?;
+
+        // The shadow listener is itself plain IPv4, so it is never dual-stack.
+        Self::new(bound_socket_v4, backlog, true, fastopen_queue_len, pollee)
+            .map_err(|(err, _)| err)
+    }
+
+    /// Enables (or disables, by passing `0`) TCP Fast Open on this listener, as requested via
+    /// `setsockopt(TCP_FASTOPEN, qlen)`.
+    ///
+    /// This only covers setting the option on an already-`listen()`ing socket; setting it before
+    /// `listen()` (also valid, and common) goes through `InitStream::set_fastopen_queue_len`
+    /// instead, whose value seeds this listener's initial queue length in [`Self::new`].
+    pub fn set_fastopen_queue_len(&self, qlen: usize) {
+        self.fastopen_queue_len.store(qlen, Ordering::Relaxed);
+    }
+
+    fn fastopen_enabled(&self) -> bool {
+        self.fastopen_queue_len.load(Ordering::Relaxed) > 0
+    }
+
     /// Append sockets listening at LocalEndPoint to support backlog
     fn fill_backlog_sockets(&self, pollee: &Pollee) -> Result<()> {
         let mut backlog_sockets = self.backlog_sockets.write();
@@ -56,34 +132,76 @@ impl ListenStream {
         }
 
         for _ in current_backlog_len..backlog {
-            let backlog_socket = BacklogSocket::new(&self.bound_socket, pollee)?;
+            let backlog_socket =
+                BacklogSocket::new(&self.bound_socket, pollee, self.fastopen_enabled())?;
             backlog_sockets.push(backlog_socket);
         }
 
         Ok(())
     }
 
+    /// Drops backlog sockets that can never become acceptable (e.g., because they were RSTed)
+    /// and replaces them with fresh ones listening at the local endpoint, so the effective
+    /// backlog stays at `self.backlog` live sockets.
+    ///
+    /// Unlike [`Self::fill_backlog_sockets`], a failure to create a replacement socket is not
+    /// propagated: the caller is usually in the middle of accepting an already-ready connection
+    /// and a temporary dip in the backlog size shouldn't fail that.
+    fn reap_dead_backlog_sockets(&self, backlog_sockets: &mut Vec<BacklogSocket>, pollee: &Pollee) {
+        backlog_sockets.retain(|backlog_socket| !backlog_socket.is_dead());
+
+        while backlog_sockets.len() < self.backlog {
+            let Ok(backlog_socket) =
+                BacklogSocket::new(&self.bound_socket, pollee, self.fastopen_enabled())
+            else {
+                break;
+            };
+            backlog_sockets.push(backlog_socket);
+        }
+    }
+
     pub fn try_accept(&self, pollee: &Pollee) -> Result<ConnectedStream> {
+        if let Ok((bound_socket, remote_endpoint)) = self.accept_from_backlog(pollee) {
+            return Ok(ConnectedStream::new(bound_socket, remote_endpoint, false));
+        }
+
+        // Only a dual-stack IPv6 listener has a shadow IPv4 listener to fall back to; an IPv4
+        // peer connecting to it must be exposed as an IPv4-mapped IPv6 peer address.
+        let dual_stack_v4 = self.dual_stack_v4.as_ref().ok_or_else(|| {
+            Error::with_message(Errno::EAGAIN, "no pending connection is available")
+        })?;
+        let (bound_socket, remote_endpoint) = dual_stack_v4.accept_from_backlog(pollee)?;
+        Ok(ConnectedStream::new(
+            bound_socket,
+            to_v4_mapped(remote_endpoint),
+            false,
+        ))
+    }
+
+    /// Accepts a connection from this listener's own backlog, without considering
+    /// [`Self::dual_stack_v4`].
+    fn accept_from_backlog(&self, pollee: &Pollee) -> Result<(BoundTcpSocket, IpEndpoint)> {
         let mut backlog_sockets = self.backlog_sockets.write();
 
+        self.reap_dead_backlog_sockets(&mut backlog_sockets, pollee);
+
+        let fastopen_enabled = self.fastopen_enabled();
         let index = backlog_sockets
             .iter()
-            .position(|backlog_socket| backlog_socket.can_accept())
+            .position(|backlog_socket| backlog_socket.can_accept(fastopen_enabled))
             .ok_or_else(|| {
                 Error::with_message(Errno::EAGAIN, "no pending connection is available")
             })?;
         let active_backlog_socket = backlog_sockets.remove(index);
 
-        if let Ok(backlog_socket) = BacklogSocket::new(&self.bound_socket, pollee) {
+        if let Ok(backlog_socket) =
+            BacklogSocket::new(&self.bound_socket, pollee, fastopen_enabled)
+        {
             backlog_sockets.push(backlog_socket);
         }
 
         let remote_endpoint = active_backlog_socket.remote_endpoint().unwrap();
-        Ok(ConnectedStream::new(
-            active_backlog_socket.into_bound_socket(),
-            remote_endpoint,
-            false,
-        ))
+        Ok((active_backlog_socket.into_bound_socket(), remote_endpoint))
     }
 
     pub fn local_endpoint(&self) -> IpEndpoint {
@@ -94,10 +212,21 @@ impl ListenStream {
         self.bound_socket.iface()
     }
 
-    pub(super) fn check_io_events(&self) -> IoEvents {
-        let backlog_sockets = self.backlog_sockets.read();
+    pub(super) fn check_io_events(&self, pollee: &Pollee) -> IoEvents {
+        let mut backlog_sockets = self.backlog_sockets.write();
 
-        let can_accept = backlog_sockets.iter().any(|socket| socket.can_accept());
+        // Without reaping, a backlog made entirely of dead (e.g., RSTed) sockets would report
+        // nothing acceptable forever, even though fresh connections could otherwise arrive.
+        self.reap_dead_backlog_sockets(&mut backlog_sockets, pollee);
+
+        let fastopen_enabled = self.fastopen_enabled();
+        let can_accept = backlog_sockets
+            .iter()
+            .any(|socket| socket.can_accept(fastopen_enabled))
+            || self
+                .dual_stack_v4
+                .as_ref()
+                .is_some_and(|v4| v4.check_io_events(pollee).contains(IoEvents::IN));
 
         // If network packets come in simultaneously, the socket state may change in the middle.
         // However, the current pollee implementation should be able to handle this race condition.
@@ -115,6 +244,10 @@ impl ListenStream {
         &mut self,
         set_option: impl Fn(&mut dyn RawTcpSetOption) -> R,
     ) -> R {
+        if let Some(dual_stack_v4) = &mut self.dual_stack_v4 {
+            dual_stack_v4.set_raw_option(&set_option);
+        }
+
         self.backlog_sockets.write().iter_mut().for_each(|socket| {
             if socket
                 .bound_socket
@@ -140,19 +273,26 @@ struct BacklogSocket {
 impl BacklogSocket {
     // FIXME: All of the error codes below seem to have no Linux equivalents, and I see no reason
     // why the error may occur. Perhaps it is better to call `unwrap()` directly?
-    fn new(bound_socket: &BoundTcpSocket, pollee: &Pollee) -> Result<Self> {
+    fn new(bound_socket: &BoundTcpSocket, pollee: &Pollee, fastopen_enabled: bool) -> Result<Self> {
         let local_endpoint = bound_socket.local_endpoint().ok_or(Error::with_message(
             Errno::EINVAL,
             "the socket is not bound",
         ))?;
+        // `local_endpoint.addr` may be unspecified here (e.g. `0.0.0.0`/`::`), which is exactly
+        // the case for a wildcard listener. That's fine: an unspecified address means "listen on
+        // every local address", not "no address yet" — `listen()` below only rejects port `0`,
+        // and by this point the listener has always already had a concrete port assigned (either
+        // the caller's, or an ephemeral one picked when `listen()` auto-bound it).
+        debug_assert_ne!(local_endpoint.port, 0, "a listening socket must have a concrete port");
 
         let unbound_socket = {
             let mut unbound = UnboundTcpSocket::new();
-            unbound.set_keep_alive(bound_socket.raw_with(|socket| socket.keep_alive()));
-            unbound.set_nagle_enabled(bound_socket.raw_with(|socket| socket.nagle_enabled()));
-
-            // TODO: Inherit other options that can be set via `setsockopt` from bound socket
-
+            inherit_raw_options(bound_socket, &mut unbound);
+            if fastopen_enabled {
+                // Let the raw socket generate and verify Fast Open cookies against this boot's
+                // key, and queue a SYN's payload immediately once its cookie checks out.
+                unbound.set_fastopen_key(Some(fastopen::key()));
+            }
             Box::new(unbound)
         };
         let bound_socket = {
@@ -186,11 +326,29 @@ impl BacklogSocket {
     ///
     /// The Linux kernel implementation can be found at
     /// <https://elixir.bootlin.com/linux/v6.11.8/source/net/ipv4/tcp_input.c#L7304>.
-    //
-    // FIMXE: Some sockets may be dead (e.g., RSTed), and such sockets can never become alive
-    // again. We need to remove them from the backlog sockets.
-    fn can_accept(&self) -> bool {
-        self.bound_socket.raw_with(|socket| socket.may_send())
+    ///
+    /// When `fastopen_enabled` is set, a socket that received a SYN carrying a valid Fast Open
+    /// cookie is also considered acceptable as soon as the SYN's payload has been queued, rather
+    /// than only once the handshake reaches ESTABLISHED.
+    fn can_accept(&self, fastopen_enabled: bool) -> bool {
+        self.bound_socket.raw_with(|socket| {
+            can_accept_state(
+                socket.may_send(),
+                socket.state(),
+                fastopen_enabled,
+                socket.fastopen_data_queued(),
+            )
+        })
+    }
+
+    /// Returns whether the backlog socket is dead, i.e., it can never become acceptable again.
+    ///
+    /// This happens when the connection was reset (or otherwise torn down) before it was ever
+    /// accepted: the raw socket has already dropped into `Closed`/`TimeWait` without having
+    /// reached a state where [`Self::can_accept`] would return `true`.
+    fn is_dead(&self) -> bool {
+        self.bound_socket
+            .raw_with(|socket| is_dead_state(socket.state(), socket.may_send()))
     }
 
     fn remote_endpoint(&self) -> Option<IpEndpoint> {
@@ -202,3 +360,142 @@ impl BacklogSocket {
         self.bound_socket
     }
 }
+
+/// The pure decision behind [`BacklogSocket::can_accept`], pulled out of the method so it can be
+/// unit-tested without a live `BoundTcpSocket`.
+fn can_accept_state(
+    may_send: bool,
+    state: TcpState,
+    fastopen_enabled: bool,
+    fastopen_data_queued: bool,
+) -> bool {
+    if may_send {
+        return true;
+    }
+
+    fastopen_enabled && state == TcpState::SynReceived && fastopen_data_queued
+}
+
+/// The pure decision behind [`BacklogSocket::is_dead`], pulled out of the method so it can be
+/// unit-tested without a live `BoundTcpSocket`.
+fn is_dead_state(state: TcpState, may_send: bool) -> bool {
+    matches!(state, TcpState::Closed | TcpState::TimeWait) && !may_send
+}
+
+/// Converts an IPv4 endpoint into the IPv4-mapped IPv6 endpoint (`::ffff:a.b.c.d`) it should be
+/// reported as on a dual-stack IPv6 listener (e.g., via `accept`'s peer address or `getpeername`).
+fn to_v4_mapped(endpoint: IpEndpoint) -> IpEndpoint {
+    let IpAddress::Ipv4(v4_addr) = endpoint.addr else {
+        return endpoint;
+    };
+
+    let mut segments = [0u8; 16];
+    segments[10] = 0xff;
+    segments[11] = 0xff;
+    segments[12..].copy_from_slice(&v4_addr.octets());
+
+    IpEndpoint::new(IpAddress::Ipv6(Ipv6Address::from_bytes(&segments)), endpoint.port)
+}
+
+/// Copies the `setsockopt`-configurable TCP/socket options from `src` (typically the listening
+/// socket, or a `Listen`-state backlog socket) onto `dst`, so that a newly accepted connection
+/// doesn't silently lose the options the listener was configured with.
+///
+/// This is wiring only: the getters/setters below (`keep_alive`, `recv_buffer_size`, etc.) are
+/// options `aster_bigtcp`'s `RawTcpSetOption`/`UnboundTcpSocket` already expose for `setsockopt`
+/// on an individual socket; nothing here adds new option storage to that crate, it just copies
+/// the existing per-option state from one socket to another at backlog-socket creation time.
+fn inherit_raw_options(src: &BoundTcpSocket, dst: &mut UnboundTcpSocket) {
+    src.raw_with(|socket| {
+        dst.set_keep_alive(socket.keep_alive());
+        dst.set_keep_idle(socket.keep_idle());
+        dst.set_keep_interval(socket.keep_interval());
+        dst.set_keep_count(socket.keep_count());
+        dst.set_nagle_enabled(socket.nagle_enabled());
+        dst.set_user_timeout(socket.user_timeout());
+        dst.set_recv_buffer_size(socket.recv_buffer_size());
+        dst.set_send_buffer_size(socket.send_buffer_size());
+        dst.set_max_segment_size(socket.max_segment_size());
+    });
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn v4_mapped_address_carries_port_and_octets() {
+        let v4_endpoint = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::new(192, 0, 2, 1)), 8080);
+        let mapped = to_v4_mapped(v4_endpoint);
+
+        assert_eq!(mapped.port, 8080);
+        let IpAddress::Ipv6(v6_addr) = mapped.addr else {
+            panic!("expected an IPv6 address");
+        };
+        assert_eq!(
+            v6_addr.octets(),
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 192, 0, 2, 1]
+        );
+    }
+
+    #[ktest]
+    fn v6_endpoint_passes_through_unmapped() {
+        let v6_endpoint = IpEndpoint::new(
+            IpAddress::Ipv6(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            443,
+        );
+        assert_eq!(to_v4_mapped(v6_endpoint), v6_endpoint);
+    }
+
+    #[ktest]
+    fn established_socket_can_always_accept() {
+        assert!(can_accept_state(true, TcpState::Established, false, false));
+        assert!(can_accept_state(true, TcpState::SynReceived, false, false));
+    }
+
+    #[ktest]
+    fn fastopen_lets_syn_received_accept_once_data_is_queued() {
+        assert!(can_accept_state(
+            false,
+            TcpState::SynReceived,
+            true,
+            true
+        ));
+        assert!(!can_accept_state(
+            false,
+            TcpState::SynReceived,
+            true,
+            false
+        ));
+    }
+
+    #[ktest]
+    fn fastopen_disabled_never_accepts_before_the_handshake_completes() {
+        assert!(!can_accept_state(
+            false,
+            TcpState::SynReceived,
+            false,
+            true
+        ));
+    }
+
+    #[ktest]
+    fn closed_or_timewait_without_may_send_is_dead() {
+        assert!(is_dead_state(TcpState::Closed, false));
+        assert!(is_dead_state(TcpState::TimeWait, false));
+    }
+
+    #[ktest]
+    fn closed_socket_that_can_still_send_is_not_dead() {
+        // This shouldn't happen in practice, but the predicate is defined purely in terms of its
+        // inputs, so it's worth pinning down that `may_send` always takes priority.
+        assert!(!is_dead_state(TcpState::Closed, true));
+    }
+
+    #[ktest]
+    fn syn_received_is_not_dead() {
+        assert!(!is_dead_state(TcpState::SynReceived, false));
+    }
+}
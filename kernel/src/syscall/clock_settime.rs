@@ -1,9 +1,11 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
 use super::SyscallReturn;
 use crate::{
     prelude::*,
-    time::{
-        clockid_t,
-    },
+    time::{check_can_set_clock, clockid_t, timespec_t, SystemTime, CLOCK_REALTIME},
 };
 
 pub fn sys_clock_settime(
@@ -12,6 +14,22 @@ pub fn sys_clock_settime(
     ctx: &Context,
 ) -> Result<SyscallReturn> {
     debug!("clockid = {:?}", clockid);
-    unimplemented!();
+
+    if clockid != CLOCK_REALTIME {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "only CLOCK_REALTIME can be set with clock_settime"
+        );
+    }
+
+    check_can_set_clock(ctx)?;
+
+    let timespec: timespec_t = ctx.user_space().read_val(timespec_addr)?;
+    if !timespec.is_valid() {
+        return_errno_with_message!(Errno::EINVAL, "the timespec is invalid");
+    }
+
+    SystemTime::from(Duration::from(timespec)).set_as_now()?;
+
     Ok(SyscallReturn::Return(0))
 }
@@ -1,9 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
 use super::SyscallReturn;
 use crate::{
     prelude::*,
-    time::{timeval_t, SystemTime},
+    time::{check_can_set_clock, timeval_t, SystemTime},
 };
 
 // The use of the timezone structure is obsolete.
@@ -12,7 +14,14 @@ pub fn sys_settimeofday(
     timeval_addr: Vaddr,
     /* timezone_addr: Vaddr, */ ctx: &Context,
 ) -> Result<SyscallReturn> {
-    unimplemented!();
+    check_can_set_clock(ctx)?;
+
+    let timeval: timeval_t = ctx.user_space().read_val(timeval_addr)?;
+    if !timeval.is_valid() {
+        return_errno_with_message!(Errno::EINVAL, "the timeval is invalid");
+    }
+
+    SystemTime::from(Duration::from(timeval)).set_as_now()?;
 
     Ok(SyscallReturn::Return(0))
 }
@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Clock types and identifiers shared by the time-related system calls.
+
+mod realtime;
+
+pub use realtime::{monotonic_boot_now, read_realtime_offset_nanos, write_realtime_offset_nanos};
+
+use core::time::Duration;
+
+use crate::prelude::*;
+
+pub type clockid_t = i32;
+
+pub const CLOCK_REALTIME: clockid_t = 0;
+pub const CLOCK_MONOTONIC: clockid_t = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: clockid_t = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: clockid_t = 3;
+pub const CLOCK_MONOTONIC_RAW: clockid_t = 4;
+pub const CLOCK_REALTIME_COARSE: clockid_t = 5;
+pub const CLOCK_MONOTONIC_COARSE: clockid_t = 6;
+pub const CLOCK_BOOTTIME: clockid_t = 7;
+
+/// A `struct timespec` as defined by POSIX, in the layout used to copy time
+/// values to and from user space.
+#[derive(Debug, Clone, Copy, Default, Pod)]
+#[repr(C)]
+pub struct timespec_t {
+    sec: i64,
+    nsec: i64,
+}
+
+impl timespec_t {
+    /// Returns whether the fields fall within the ranges POSIX allows.
+    pub fn is_valid(&self) -> bool {
+        self.sec >= 0 && (0..1_000_000_000).contains(&self.nsec)
+    }
+}
+
+impl From<timespec_t> for Duration {
+    fn from(ts: timespec_t) -> Self {
+        Duration::new(ts.sec as u64, ts.nsec as u32)
+    }
+}
+
+impl From<Duration> for timespec_t {
+    fn from(duration: Duration) -> Self {
+        Self {
+            sec: duration.as_secs() as i64,
+            nsec: duration.subsec_nanos() as i64,
+        }
+    }
+}
+
+/// A `struct timeval` as defined by POSIX, in the layout used to copy time
+/// values to and from user space.
+#[derive(Debug, Clone, Copy, Default, Pod)]
+#[repr(C)]
+pub struct timeval_t {
+    sec: i64,
+    usec: i64,
+}
+
+impl timeval_t {
+    /// Returns whether the fields fall within the ranges POSIX allows.
+    pub fn is_valid(&self) -> bool {
+        self.sec >= 0 && (0..1_000_000).contains(&self.usec)
+    }
+}
+
+impl From<timeval_t> for Duration {
+    fn from(tv: timeval_t) -> Self {
+        Duration::new(tv.sec as u64, (tv.usec as u32) * 1000)
+    }
+}
+
+impl From<Duration> for timeval_t {
+    fn from(duration: Duration) -> Self {
+        Self {
+            sec: duration.as_secs() as i64,
+            usec: duration.subsec_micros() as i64,
+        }
+    }
+}
+
+/// The kernel's view of the wall-clock (`CLOCK_REALTIME`) time.
+///
+/// The wall clock is tracked as `monotonic_boot_now() + offset`, where
+/// `offset` is a single atomically-updated value. This way, adjusting the
+/// wall clock via `clock_settime`/`settimeofday` never perturbs the
+/// monotonic clocks, and concurrent readers never observe a torn value.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemTime(Duration);
+
+impl SystemTime {
+    /// Returns the current wall-clock time.
+    pub fn now() -> Self {
+        let monotonic_nanos = monotonic_boot_now().as_nanos() as i64;
+        let wall_nanos = monotonic_nanos.saturating_add(read_realtime_offset_nanos());
+        Self(Duration::from_nanos(wall_nanos.max(0) as u64))
+    }
+
+    /// Sets the wall clock so that [`SystemTime::now`] reports `self` at the
+    /// current instant.
+    ///
+    /// There's no requirement that `self` postdate the current uptime:
+    /// Linux allows `clock_settime`/
+    /// `settimeofday` to set `CLOCK_REALTIME` to any non-negative value, so
+    /// the resulting offset from the monotonic clock may legitimately be
+    /// negative (e.g. setting an old timestamp after the system has been up
+    /// for a while).
+    pub fn set_as_now(self) -> Result<()> {
+        let monotonic_nanos = monotonic_boot_now().as_nanos() as i64;
+        let wall_nanos = i64::try_from(self.0.as_nanos()).map_err(|_| {
+            Error::with_message(Errno::EINVAL, "the requested time is out of range")
+        })?;
+        write_realtime_offset_nanos(wall_nanos.saturating_sub(monotonic_nanos));
+        Ok(())
+    }
+
+    pub fn duration_since_epoch(&self) -> Duration {
+        self.0
+    }
+}
+
+impl From<Duration> for SystemTime {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+/// Returns `EPERM` unless the caller may set the system clock
+/// (`clock_settime`/`settimeofday`), which here means its effective UID is
+/// root. Capabilities such as Linux's `CAP_SYS_TIME` are not consulted.
+pub fn check_can_set_clock(ctx: &Context) -> Result<()> {
+    if !ctx.posix_thread.credentials().euid().is_root() {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "the caller does not have the capability to set the system clock"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn timespec_round_trips_through_duration() {
+        let ts = timespec_t {
+            sec: 1_700_000_000,
+            nsec: 123_456_789,
+        };
+        let duration = Duration::from(ts);
+        assert_eq!(duration.as_secs() as i64, ts.sec);
+        assert_eq!(duration.subsec_nanos() as i64, ts.nsec);
+        assert!(timespec_t::from(duration).is_valid());
+    }
+
+    #[ktest]
+    fn timespec_rejects_out_of_range_nanoseconds() {
+        let ts = timespec_t {
+            sec: 0,
+            nsec: 1_000_000_000,
+        };
+        assert!(!ts.is_valid());
+    }
+
+    #[ktest]
+    fn timeval_round_trips_through_duration() {
+        let tv = timeval_t {
+            sec: 1_700_000_000,
+            usec: 500_000,
+        };
+        let duration = Duration::from(tv);
+        assert_eq!(duration.as_secs() as i64, tv.sec);
+        assert_eq!(duration.subsec_micros() as i64, tv.usec);
+        assert!(timeval_t::from(duration).is_valid());
+    }
+
+    #[ktest]
+    fn timeval_rejects_out_of_range_microseconds() {
+        let tv = timeval_t {
+            sec: 0,
+            usec: 1_000_000,
+        };
+        assert!(!tv.is_valid());
+    }
+
+    #[ktest]
+    fn realtime_offset_may_be_negative() {
+        // The offset storage must hold a negative value, which is what results when the
+        // wall clock is set to a time earlier than the current uptime; `clock_settime`/
+        // `settimeofday` may legitimately set `CLOCK_REALTIME` to any non-negative value
+        // regardless of how long the system has been up.
+        let saved = read_realtime_offset_nanos();
+
+        let monotonic_nanos = monotonic_boot_now().as_nanos() as i64;
+        write_realtime_offset_nanos(-(monotonic_nanos + 1));
+        assert!(read_realtime_offset_nanos() < 0);
+
+        write_realtime_offset_nanos(saved);
+    }
+}
@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The offset between the monotonic boot clock and the wall clock.
+
+use core::{
+    sync::atomic::{AtomicI64, Ordering},
+    time::Duration,
+};
+
+use ostd::arch::timer::Jiffies;
+
+/// The current `CLOCK_REALTIME` offset from [`monotonic_boot_now`], stored as
+/// a whole, possibly negative, number of nanoseconds.
+///
+/// The offset is signed because `clock_settime`/`settimeofday` may set the
+/// wall clock to any non-negative value regardless of how long the system
+/// has been up, e.g. setting a timestamp that predates the current uptime.
+/// An `i64` comfortably covers any offset we care about in either direction.
+static REALTIME_OFFSET_NANOS: AtomicI64 = AtomicI64::new(0);
+
+/// Returns the time elapsed since boot, as tracked by the monotonic clock.
+pub fn monotonic_boot_now() -> Duration {
+    Jiffies::elapsed().as_duration()
+}
+
+/// Returns the current offset applied to the monotonic clock to produce the
+/// wall-clock (`CLOCK_REALTIME`) time, in nanoseconds.
+pub fn read_realtime_offset_nanos() -> i64 {
+    REALTIME_OFFSET_NANOS.load(Ordering::Relaxed)
+}
+
+/// Updates the offset applied to the monotonic clock to produce the
+/// wall-clock (`CLOCK_REALTIME`) time, in nanoseconds.
+pub fn write_realtime_offset_nanos(offset_nanos: i64) {
+    REALTIME_OFFSET_NANOS.store(offset_nanos, Ordering::Relaxed);
+}